@@ -1,145 +1,920 @@
 // Ported from https://habr.com/en/articles/692072/
 
-use num_bigint::{BigUint, RandBigInt};
+use hmac::{Hmac, Mac};
+use num_bigint::{BigInt, BigUint, RandBigInt};
 use num_traits::{One, Zero};
 use rand::thread_rng;
-use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use std::fmt;
 
+type HmacSha256 = Hmac<Sha256>;
+
+/// Errors produced by the curve arithmetic and signing/verification
+/// routines. Introduced so the crate can be embedded as a library without
+/// risking a panic on attacker-controlled points or scalars.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EcError {
+    /// The given `(x, y)` pair does not satisfy the curve equation.
+    NotOnCurve,
+    /// `value` has no multiplicative inverse modulo `modulus` (`gcd != 1`).
+    NonInvertible,
+    /// An operation that requires a finite point received the point at infinity.
+    PointAtInfinity,
+    /// A scalar was outside its expected valid range.
+    InvalidScalar,
+}
+
+impl fmt::Display for EcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcError::NotOnCurve => write!(f, "the point is not on the curve"),
+            EcError::NonInvertible => write!(f, "value has no inverse modulo the given modulus"),
+            EcError::PointAtInfinity => write!(f, "operation is undefined at the point at infinity"),
+            EcError::InvalidScalar => write!(f, "scalar is outside its valid range"),
+        }
+    }
+}
+
+impl std::error::Error for EcError {}
+
+/// A point on a short Weierstrass curve, or the point at infinity.
+///
+/// Modeling the identity as its own variant (rather than a `(0, 0)`
+/// sentinel, which looks like an ordinary coordinate and would even fail
+/// `Point::new`'s curve check) keeps `add` and `multiply` correct near the
+/// identity instead of relying on a coordinate that isn't actually on the
+/// curve.
 #[derive(Clone, Debug, PartialEq)]
-struct Point {
-    x: BigUint,
-    y: BigUint,
-    curve_config: CurveConfig,
+enum Point {
+    Coor {
+        x: BigUint,
+        y: BigUint,
+        curve_config: CurveConfig,
+    },
+    Identity,
 }
 
+/// Parameters of a short Weierstrass curve `y^2 = x^3 + a*x + b (mod p)`
+/// together with its generator `(gx, gy)` and group order `n`. Carrying `n`
+/// and the generator here (rather than hard-coding secp256k1's order as a
+/// string literal inside `sign_message`/`verify_signature`) is what lets
+/// this file act as a named-curve registry instead of a secp256k1-only demo.
 #[derive(Clone, Debug, PartialEq)]
 struct CurveConfig {
     a: BigUint,
     b: BigUint,
     p: BigUint,
+    n: BigUint,
+    gx: BigUint,
+    gy: BigUint,
+}
+
+impl CurveConfig {
+    /// The secp256k1 curve parameters used by Bitcoin and Ethereum.
+    fn secp256k1() -> Self {
+        CurveConfig {
+            a: BigUint::zero(),
+            b: BigUint::from(7u32),
+            p: BigUint::parse_bytes(b"115792089237316195423570985008687907853269984665640564039457584007908834671663", 10).unwrap(),
+            n: BigUint::parse_bytes(b"115792089237316195423570985008687907852837564279074904382605163141518161494337", 10).unwrap(),
+            gx: BigUint::parse_bytes(b"55066263022277343669578718895168534326250603453777594175500187360389116729240", 10).unwrap(),
+            gy: BigUint::parse_bytes(b"32670510020758816978083085130507043184471273380659243275938904335757337482424", 10).unwrap(),
+        }
+    }
+
+    /// Builds a curve configuration from explicit parameters, validating
+    /// that the generator `(gx, gy)` lies on the curve and has the stated
+    /// order `n`, i.e. that `n * G` is the point at infinity *and* `n` is
+    /// prime. The primality check matters because `n * G == Identity` alone
+    /// is also satisfied by any multiple of the true order (e.g. `2 * n`),
+    /// which would silently halve the strength of the `r`/`s` reduction
+    /// done with `n` during signing and verification; named curves always
+    /// give their generator a prime order, so this rejects that case.
+    fn from_params(
+        a: BigUint,
+        b: BigUint,
+        p: BigUint,
+        n: BigUint,
+        gx: BigUint,
+        gy: BigUint,
+    ) -> Result<Self, EcError> {
+        if !is_probable_prime(&n, 40) {
+            return Err(EcError::InvalidScalar);
+        }
+        let config = CurveConfig { a, b, p, n, gx, gy };
+        let g = config.generator()?;
+        match g.multiply(&config.n)? {
+            Point::Identity => Ok(config),
+            Point::Coor { .. } => Err(EcError::InvalidScalar),
+        }
+    }
+
+    /// Returns this curve's generator point `G`.
+    fn generator(&self) -> Result<Point, EcError> {
+        Point::new(self.gx.clone(), self.gy.clone(), self.clone())
+    }
+}
+
+/// Arithmetic modulo a prime `p`. Centralizes the modular reduction and
+/// `BigUint` underflow handling (`+ p - x` when a subtraction would
+/// otherwise go negative) that used to be inlined ad hoc across `add` and
+/// `multiply`.
+struct Fp {
+    p: BigUint,
+}
+
+impl Fp {
+    fn new(p: BigUint) -> Self {
+        Fp { p }
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + b) % &self.p
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if b > a {
+            (a + &self.p - b) % &self.p
+        } else {
+            (a - b) % &self.p
+        }
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a * b) % &self.p
+    }
+
+    fn pow(&self, a: &BigUint, e: &BigUint) -> BigUint {
+        a.modpow(e, &self.p)
+    }
+
+    fn inverse(&self, a: &BigUint) -> Result<BigUint, EcError> {
+        mod_inverse(a, &self.p)
+    }
+}
+
+/// A field element paired with the `Fp` it reduces under. Pairing the value
+/// with its field (rather than leaving callers to thread `Fp::add`/`sub`/
+/// `mul` through by hand) is what lets `Point::add` below read as ordinary
+/// arithmetic (`&x1e + &x2e`, `&slope * &slope`) instead of the scattered
+/// `fp.add(...)`/`fp.mul(...)` call chains it used to be.
+#[derive(Clone)]
+struct FpElem<'a> {
+    value: BigUint,
+    fp: &'a Fp,
+}
+
+impl<'a> FpElem<'a> {
+    fn new(value: BigUint, fp: &'a Fp) -> Self {
+        FpElem { value: value % &fp.p, fp }
+    }
+
+    fn pow(&self, e: &BigUint) -> FpElem<'a> {
+        FpElem { value: self.fp.pow(&self.value, e), fp: self.fp }
+    }
+
+    fn inverse(&self) -> Result<FpElem<'a>, EcError> {
+        Ok(FpElem { value: self.fp.inverse(&self.value)?, fp: self.fp })
+    }
+}
+
+impl<'a> std::ops::Add for &FpElem<'a> {
+    type Output = FpElem<'a>;
+
+    fn add(self, other: &FpElem<'a>) -> FpElem<'a> {
+        FpElem { value: self.fp.add(&self.value, &other.value), fp: self.fp }
+    }
+}
+
+impl<'a> std::ops::Sub for &FpElem<'a> {
+    type Output = FpElem<'a>;
+
+    fn sub(self, other: &FpElem<'a>) -> FpElem<'a> {
+        FpElem { value: self.fp.sub(&self.value, &other.value), fp: self.fp }
+    }
+}
+
+impl<'a> std::ops::Mul for &FpElem<'a> {
+    type Output = FpElem<'a>;
+
+    fn mul(self, other: &FpElem<'a>) -> FpElem<'a> {
+        FpElem { value: self.fp.mul(&self.value, &other.value), fp: self.fp }
+    }
 }
 
 impl Point {
-    fn new(x: BigUint, y: BigUint, curve_config: CurveConfig) -> Self {
+    fn new(x: BigUint, y: BigUint, curve_config: CurveConfig) -> Result<Self, EcError> {
         let rhs = (&x * &x * &x + &curve_config.a * &x + &curve_config.b) % &curve_config.p;
         let lhs = (&y * &y) % &curve_config.p;
         if lhs != rhs {
-            panic!("The point is not on the curve");
+            return Err(EcError::NotOnCurve);
         }
-        Point { x, y, curve_config }
+        Ok(Point::Coor { x, y, curve_config })
     }
 
-    fn add(&self, other: &Point) -> Point {
-        let p = &self.curve_config.p;
+    fn add(&self, other: &Point) -> Result<Point, EcError> {
+        let (x1, y1, curve_config) = match self {
+            Point::Identity => return Ok(other.clone()),
+            Point::Coor { x, y, curve_config } => (x, y, curve_config),
+        };
+        let (x2, y2) = match other {
+            Point::Identity => return Ok(self.clone()),
+            Point::Coor { x, y, .. } => (x, y),
+        };
         // Case when adding point to itself.
-        if self.x == other.x && (self.y != other.y || self.y.is_zero()) {
-            // Return the point at infinity represented as (0, 0) in this context.
-            return Point::new(BigUint::zero(), BigUint::zero(), self.curve_config.clone());
+        if x1 == x2 && (y1 != y2 || y1.is_zero()) {
+            return Ok(Point::Identity);
         }
-        let slope = if self.x == other.x {
+        let fp = Fp::new(curve_config.p.clone());
+        let x1e = FpElem::new(x1.clone(), &fp);
+        let y1e = FpElem::new(y1.clone(), &fp);
+        let x2e = FpElem::new(x2.clone(), &fp);
+        let y2e = FpElem::new(y2.clone(), &fp);
+        let a = FpElem::new(curve_config.a.clone(), &fp);
+        let two = FpElem::new(BigUint::from(2u32), &fp);
+        let three = FpElem::new(BigUint::from(3u32), &fp);
+        let exp_two = BigUint::from(2u32);
+
+        let slope = if x1 == x2 {
             // Doubling a point.
-            let numerator = 3u32 * &self.x * &self.x + &self.curve_config.a;
-            let denominator = 2u32 * &self.y;
-            (numerator * mod_inverse(&denominator, p)) % p
+            let numerator = &(&three * &x1e.pow(&exp_two)) + &a;
+            let denominator = &two * &y1e;
+            &numerator * &denominator.inverse()?
         } else {
             // Adding two distinct points.
-            let numerator = if &other.y < &self.y {
-                &other.y + p - &self.y
-            } else {
-                &other.y - &self.y
-            };
-            let denominator = if &other.x < &self.x {
-                &other.x + p - &self.x
-            } else {
-                &other.x - &self.x
-            };
-            (numerator * mod_inverse(&denominator, p)) % p
+            let numerator = &y2e - &y1e;
+            let denominator = &x2e - &x1e;
+            &numerator * &denominator.inverse()?
+        };
+        let x3 = &slope.pow(&exp_two) - &(&x1e + &x2e);
+        let y3 = &(&slope * &(&x1e - &x3)) - &y1e;
+        Point::new(x3.value, y3.value, curve_config.clone())
+    }
+
+    /// Scalar multiplication via left-to-right binary double-and-add:
+    /// iterates over the bits of `times` from most to least significant,
+    /// doubling an accumulator each step and adding `self` whenever the bit
+    /// is set. This replaces the previous approach of growing and linearly
+    /// scanning a `Vec` of every intermediate coefficient, which was both
+    /// memory-hungry and effectively linear in the scalar's magnitude.
+    fn multiply(&self, times: &BigUint) -> Result<Point, EcError> {
+        let mut acc = Point::Identity;
+        for i in (0..times.bits()).rev() {
+            acc = acc.add(&acc)?;
+            if times.bit(i) {
+                acc = acc.add(self)?;
+            }
+        }
+        Ok(acc)
+    }
+
+    /// Serializes the point as a 33-byte compressed encoding: the 32-byte
+    /// big-endian `x` coordinate followed by a single parity byte (`0x02`
+    /// for even `y`, `0x03` for odd `y`). The parity byte takes SEC1's
+    /// prefix values but comes after `x`, so this is not the SEC1 layout.
+    fn compress(&self) -> Result<[u8; 33], EcError> {
+        let (x, y) = match self {
+            Point::Coor { x, y, .. } => (x, y),
+            Point::Identity => return Err(EcError::PointAtInfinity),
+        };
+        let mut out = [0u8; 33];
+        let x_bytes = x.to_bytes_be();
+        out[32 - x_bytes.len()..32].copy_from_slice(&x_bytes);
+        out[32] = if y.bit(0) { 0x03 } else { 0x02 };
+        Ok(out)
+    }
+
+    /// Recovers a point from its 33-byte compressed encoding by solving
+    /// `y^2 = x^3 + a*x + b (mod p)` for `y` and selecting the root whose
+    /// parity matches the stored bit.
+    fn decompress(bytes: &[u8; 33], curve_config: CurveConfig) -> Result<Point, EcError> {
+        let x = BigUint::from_bytes_be(&bytes[..32]);
+        if x >= curve_config.p {
+            return Err(EcError::NotOnCurve);
+        }
+        let want_odd = bytes[32] == 0x03;
+        let rhs = (&x * &x * &x + &curve_config.a * &x + &curve_config.b) % &curve_config.p;
+        let y = modsqrt(&rhs, &curve_config.p)?;
+        let y = if y.bit(0) == want_odd {
+            y
+        } else if y.is_zero() {
+            // 0 has a single square root (itself); flipping it as `p - 0`
+            // would produce the non-canonical value `p` instead of a
+            // genuine parity mismatch, so treat this as "no such point".
+            return Err(EcError::NotOnCurve);
+        } else {
+            &curve_config.p - &y
         };
-        let x3 = (&slope * &slope + p - &self.x - &other.x) % p;
-        let y3 = (slope * (&self.x + p - &x3) - &self.y + p) % p;
-        Point::new(x3, y3, self.curve_config.clone())
-    }
-
-    fn multiply(&self, times: &BigUint) -> Point {
-        let mut current_point = self.clone();
-        let mut current_coefficient = BigUint::one();
-        let mut previous_points: Vec<(BigUint, Point)> = Vec::new();
-        while &current_coefficient < times {
-            previous_points.push((current_coefficient.clone(), current_point.clone()));
-            if &(&current_coefficient * 2u32) <= times {
-                current_point = current_point.add(&current_point);
-                current_coefficient *= 2u32;
-            } else {
-                let mut next_point = self.clone();
-                let mut next_coefficient = BigUint::one();
-                for (previous_coefficient, previous_point) in previous_points.iter().rev() {
-                    if previous_coefficient + &current_coefficient <= *times {
-                        next_coefficient = previous_coefficient.clone();
-                        next_point = previous_point.clone();
-                        break; // Found the largest usable previous point
-                    }
-                }
-                current_point = current_point.add(&next_point);
-                current_coefficient += next_coefficient;
+        Point::new(x, y, curve_config)
+    }
+
+    fn x(&self) -> Result<&BigUint, EcError> {
+        match self {
+            Point::Coor { x, .. } => Ok(x),
+            Point::Identity => Err(EcError::PointAtInfinity),
+        }
+    }
+
+    fn y(&self) -> Result<&BigUint, EcError> {
+        match self {
+            Point::Coor { y, .. } => Ok(y),
+            Point::Identity => Err(EcError::PointAtInfinity),
+        }
+    }
+}
+
+// Operator overloads so callers can write `&p1 + &p2` and `&g * &k` instead
+// of the checked `Point::add`/`Point::multiply`. Both panic on the
+// (curve-invariant-violating) error cases that the checked methods report
+// via `Result`; reach for `add`/`multiply` directly when that's a concern.
+
+impl std::ops::Add<&Point> for &Point {
+    type Output = Point;
+
+    fn add(self, other: &Point) -> Point {
+        Point::add(self, other).expect("point addition should not fail for valid curve points")
+    }
+}
+
+impl std::ops::Mul<&BigUint> for &Point {
+    type Output = Point;
+
+    fn mul(self, scalar: &BigUint) -> Point {
+        Point::multiply(self, scalar).expect("scalar multiplication should not fail for valid curve points")
+    }
+}
+
+/// Computes a square root of `value` modulo the prime `modulus` via the
+/// Tonelli–Shanks algorithm, returning `Err(EcError::NotOnCurve)` when
+/// `value` is a quadratic non-residue.
+fn modsqrt(value: &BigUint, modulus: &BigUint) -> Result<BigUint, EcError> {
+    if value.is_zero() {
+        return Ok(BigUint::zero());
+    }
+    let one = BigUint::one();
+    let two = BigUint::from(2u32);
+    let p_minus_one = modulus - &one;
+
+    // Euler's criterion: value must be a quadratic residue.
+    if value.modpow(&(&p_minus_one / &two), modulus) != one {
+        return Err(EcError::NotOnCurve);
+    }
+
+    // Factor p - 1 = q * 2^s with q odd.
+    let mut q = p_minus_one.clone();
+    let mut s = 0u32;
+    while (&q % &two).is_zero() {
+        q /= &two;
+        s += 1;
+    }
+
+    if s == 1 {
+        // p ≡ 3 (mod 4), as for secp256k1: y = value^((p+1)/4) mod p.
+        return Ok(value.modpow(&((modulus + &one) / BigUint::from(4u32)), modulus));
+    }
+
+    // Find a quadratic non-residue z.
+    let neg_one = &p_minus_one % modulus;
+    let mut z = two.clone();
+    while z.modpow(&(&p_minus_one / &two), modulus) != neg_one {
+        z += &one;
+    }
+
+    let mut m = s;
+    let mut c = z.modpow(&q, modulus);
+    let mut t = value.modpow(&q, modulus);
+    let mut r = value.modpow(&((&q + &one) / &two), modulus);
+
+    while t != one {
+        // Find the least i, 0 < i < m, such that t^(2^i) == 1.
+        let mut i = 0u32;
+        let mut t2i = t.clone();
+        while t2i != one {
+            t2i = (&t2i * &t2i) % modulus;
+            i += 1;
+        }
+        let exp = BigUint::one() << (m - i - 1) as usize;
+        let b = c.modpow(&exp, modulus);
+        m = i;
+        c = (&b * &b) % modulus;
+        t = (&t * &c) % modulus;
+        r = (&r * &b) % modulus;
+    }
+
+    Ok(r)
+}
+
+/// Computes the modular inverse of `value` modulo `modulus` via the extended
+/// Euclidean algorithm, returning `Err(NonInvertible)` when
+/// `gcd(value, modulus) != 1` instead of assuming `modulus` is prime.
+fn mod_inverse(value: &BigUint, modulus: &BigUint) -> Result<BigUint, EcError> {
+    let (gcd, x, _) = extended_gcd(&BigInt::from(value.clone()), &BigInt::from(modulus.clone()));
+    if gcd != BigInt::one() {
+        return Err(EcError::NonInvertible);
+    }
+    let m = BigInt::from(modulus.clone());
+    let inverse = ((x % &m) + &m) % &m;
+    Ok(inverse
+        .to_biguint()
+        .expect("reduced modulo a positive modulus, so always non-negative"))
+}
+
+/// Miller–Rabin primality test. Named curves always give `n` as a prime
+/// order, so `CurveConfig::from_params` uses this to reject a caller-claimed
+/// `n` that is merely *a* multiple of the true order (e.g. `2 * n`) rather
+/// than the minimal one, which `n * G == Identity` alone cannot catch.
+fn is_probable_prime(n: &BigUint, rounds: u32) -> bool {
+    let one = BigUint::one();
+    let two = BigUint::from(2u32);
+    if *n < two {
+        return false;
+    }
+    // 3 is handled here because the witness range [2, n - 1) below is
+    // empty for it.
+    if *n == two || *n == BigUint::from(3u32) {
+        return true;
+    }
+    if (n % &two).is_zero() {
+        return false;
+    }
+
+    // Write n - 1 = d * 2^s with d odd.
+    let n_minus_one = n - &one;
+    let mut d = n_minus_one.clone();
+    let mut s = 0u32;
+    while (&d % &two).is_zero() {
+        d /= &two;
+        s += 1;
+    }
+
+    let mut rng = thread_rng();
+    'witness: for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&two, &(n - &one));
+        let mut x = a.modpow(&d, n);
+        if x == one || x == n_minus_one {
+            continue;
+        }
+        for _ in 0..s - 1 {
+            x = (&x * &x) % n;
+            if x == n_minus_one {
+                continue 'witness;
             }
         }
-        current_point
+        return false;
     }
+    true
 }
 
-fn mod_inverse(value: &BigUint, modulus: &BigUint) -> BigUint {
-    value.modpow(&(modulus - &BigUint::from(2u32)), modulus)
+/// Returns `(gcd, x, y)` such that `a * x + b * y == gcd`.
+fn extended_gcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    if b.is_zero() {
+        (a.clone(), BigInt::one(), BigInt::zero())
+    } else {
+        let (gcd, x1, y1) = extended_gcd(b, &(a % b));
+        let y = x1 - (a / b) * &y1;
+        (gcd, y1, y)
+    }
 }
 
-fn sign_message(message: &BigUint, private_key: &BigUint, g_point: &Point) -> (BigUint, BigUint) {
-    let n = BigUint::from_str("115792089237316195423570985008687907852837564279074904382605163141518161494337").unwrap();
+fn sign_message(
+    message: &BigUint,
+    private_key: &BigUint,
+    curve_config: &CurveConfig,
+) -> Result<(BigUint, BigUint), EcError> {
+    let n = &curve_config.n;
+    let g_point = curve_config.generator()?;
     let mut rng = thread_rng();
     // Generate a random k within the range [1, n-1]
-    let k = rng.gen_biguint_range(&BigUint::one(), &n);
+    let k = rng.gen_biguint_range(&BigUint::one(), n);
 
-    let r_point = g_point.multiply(&k);
-    let r = &r_point.x % &n;
+    let r_point = g_point.multiply(&k)?;
+    let r = r_point.x()? % n;
     if r == BigUint::zero() {
-        return sign_message(message, private_key, g_point);
+        return sign_message(message, private_key, curve_config);
     }
-    let k_inverse = mod_inverse(&k, &n);
-    let s = (&k_inverse * (message + &r * private_key)) % &n;
-    (r, s)
+    let k_inverse = mod_inverse(&k, n)?;
+    let s = (&k_inverse * (message + &r * private_key)) % n;
+    Ok((r, s))
 }
 
-fn verify_signature(signature: &(BigUint, BigUint), message: &BigUint, public_key: &Point, g_point: &Point) -> bool {
-    let n = BigUint::from_str("115792089237316195423570985008687907852837564279074904382605163141518161494337").unwrap();
+/// SHA-256-hashes `msg` and reduces the digest modulo the curve order `n`
+/// to form the integer `sign_message` signs, so arbitrary-length byte
+/// messages can be signed directly instead of requiring the caller to
+/// already have a `BigUint` in range.
+fn sign_bytes(
+    msg: &[u8],
+    private_key: &BigUint,
+    curve_config: &CurveConfig,
+) -> Result<(BigUint, BigUint), EcError> {
+    let digest = Sha256::digest(msg);
+    let e = BigUint::from_bytes_be(&digest) % &curve_config.n;
+    sign_message(&e, private_key, curve_config)
+}
+
+/// Like [`sign_bytes`], but derives the nonce `k` deterministically per
+/// RFC 6979 (HMAC-SHA256) instead of drawing it from `thread_rng`, so the
+/// nonce depends only on the key and the message digest: signing the same
+/// message with the same key always yields the same signature, and no
+/// source of randomness is needed.
+fn sign_bytes_deterministic(
+    msg: &[u8],
+    private_key: &BigUint,
+    curve_config: &CurveConfig,
+) -> Result<(BigUint, BigUint), EcError> {
+    let n = &curve_config.n;
+    let g_point = curve_config.generator()?;
+    let digest = Sha256::digest(msg);
+    let e = BigUint::from_bytes_be(&digest) % n;
+
+    let mut attempt = 0u32;
+    loop {
+        let k = rfc6979_nonce(private_key, &digest, n, attempt)?;
+        let r_point = g_point.multiply(&k)?;
+        let r = match r_point.x() {
+            Ok(x) => x % n,
+            Err(_) => {
+                attempt += 1;
+                continue;
+            }
+        };
+        if r.is_zero() {
+            attempt += 1;
+            continue;
+        }
+        let k_inverse = mod_inverse(&k, n)?;
+        let s = (&k_inverse * (&e + &r * private_key)) % n;
+        if s.is_zero() {
+            attempt += 1;
+            continue;
+        }
+        return Ok((r, s));
+    }
+}
+
+/// Derives a deterministic nonce `k` per RFC 6979 (section 3.2) from
+/// `private_key` and the message digest `hash`, using HMAC-SHA256 as the
+/// underlying PRF. `attempt` walks the retry branch of step (h): each
+/// attempt beyond the first redraws `K`/`V` as `K = HMAC_K(V || 0x00)`,
+/// `V = HMAC_K(V)` before deriving a fresh candidate, exactly as the spec
+/// does when a previous candidate was rejected (`r == 0` or `k == 0`).
+/// The candidate is a single 32-byte HMAC block reduced modulo `n`, and
+/// `h1` is the whole digest reduced modulo `n`: RFC 6979's truncation of
+/// both to the bit length of `n` and its rejection of candidates `>= n`
+/// are not performed, so the result matches the RFC only for a 256-bit `n`
+/// and a block below `n`.
+fn rfc6979_nonce(
+    private_key: &BigUint,
+    hash: &[u8],
+    n: &BigUint,
+    attempt: u32,
+) -> Result<BigUint, EcError> {
+    let qlen = (n.bits() as usize).div_ceil(8);
+    let priv_bytes = biguint_to_fixed_bytes(private_key, qlen);
+    // Step 3.2(a)/(c): h1 goes in as bits2octets(h1), not the raw digest —
+    // bits2int interprets the digest as an integer and bits2octets then
+    // reduces it mod n before re-encoding to qlen bytes, which matters
+    // whenever that integer is >= n.
+    let h1 = biguint_to_fixed_bytes(&(BigUint::from_bytes_be(hash) % n), qlen);
+
+    let mut v = vec![0x01u8; 32];
+    let mut k = vec![0x00u8; 32];
+
+    let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+    mac.update(&v);
+    mac.update(&[0x00]);
+    mac.update(&priv_bytes);
+    mac.update(&h1);
+    k = mac.finalize().into_bytes().to_vec();
+
+    let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+    mac.update(&v);
+    v = mac.finalize().into_bytes().to_vec();
+
+    let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+    mac.update(&v);
+    mac.update(&[0x01]);
+    mac.update(&priv_bytes);
+    mac.update(&h1);
+    k = mac.finalize().into_bytes().to_vec();
+
+    let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+    mac.update(&v);
+    v = mac.finalize().into_bytes().to_vec();
+
+    for _ in 0..attempt {
+        // Step (h)'s retry branch: a previous candidate was rejected, so
+        // advance V past the block that candidate was drawn from, then
+        // redraw K and V before trying again.
+        let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+        mac.update(&v);
+        v = mac.finalize().into_bytes().to_vec();
+
+        let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+        mac.update(&v);
+        mac.update(&[0x00]);
+        k = mac.finalize().into_bytes().to_vec();
+
+        let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+        mac.update(&v);
+        v = mac.finalize().into_bytes().to_vec();
+    }
+
+    // Step (h.2): the candidate T is derived from one more V = HMAC_K(V),
+    // not from the V left over at the end of the K/V setup above.
+    let mut mac = HmacSha256::new_from_slice(&k).expect("HMAC accepts keys of any length");
+    mac.update(&v);
+    v = mac.finalize().into_bytes().to_vec();
+
+    Ok(BigUint::from_bytes_be(&v) % n)
+}
+
+/// Left-pads `value`'s big-endian bytes to exactly `len` bytes.
+fn biguint_to_fixed_bytes(value: &BigUint, len: usize) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let mut out = vec![0u8; len];
+    out[len - bytes.len()..].copy_from_slice(&bytes);
+    out
+}
+
+fn verify_signature(
+    signature: &(BigUint, BigUint),
+    message: &BigUint,
+    public_key: &Point,
+    curve_config: &CurveConfig,
+) -> Result<bool, EcError> {
+    let n = &curve_config.n;
+    let g_point = curve_config.generator()?;
     let (r, s) = signature;
-    let s_inverse = mod_inverse(s, &n);
-    let u = message * &s_inverse % &n;
-    let v = r * &s_inverse % &n;
-    let c_point = g_point.multiply(&u).add(&public_key.multiply(&v));
-    c_point.x == *r
-}
-
-fn main() {
-    let curve_config = CurveConfig {
-        a: BigUint::zero(),
-        b: BigUint::from(7u32),
-        p: BigUint::parse_bytes(b"115792089237316195423570985008687907853269984665640564039457584007908834671663", 10).unwrap(),
-    };
-    let g_x = BigUint::parse_bytes(b"55066263022277343669578718895168534326250603453777594175500187360389116729240", 10).unwrap();
-    let g_y = BigUint::parse_bytes(b"32670510020758816978083085130507043184471273380659243275938904335757337482424", 10).unwrap();
-    let g_point = Point::new(g_x, g_y, curve_config.clone());
+    // r and s must lie in [1, n-1]; anything else is simply an invalid
+    // signature, not an error, so attacker-controlled signature data never
+    // forces an `Err` out of a verifier a caller might `?`-propagate.
+    if r.is_zero() || r >= n || s.is_zero() || s >= n {
+        return Ok(false);
+    }
+    let s_inverse = mod_inverse(s, n)?;
+    let u = message * &s_inverse % n;
+    let v = r * &s_inverse % n;
+    let c_point = g_point.multiply(&u)?.add(&public_key.multiply(&v)?)?;
+    // A recovered point at infinity can never legitimately equal r, so
+    // reject it explicitly rather than erroring out of `x()`.
+    match c_point.x() {
+        Ok(x) => Ok(*x == *r),
+        Err(EcError::PointAtInfinity) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn main() -> Result<(), EcError> {
+    let curve_config = CurveConfig::secp256k1();
+    let g_point = curve_config.generator()?;
 
     // Example usage
     let private_key = BigUint::from(123456789012345u64);
-    let public_key = g_point.multiply(&private_key);
-    assert_eq!(public_key.x, BigUint::parse_bytes(b"10781230418046409857141107048746558306281905541083370272873392624066644885158", 10).unwrap());
-    assert_eq!(public_key.y, BigUint::parse_bytes(b"75292686749126855329828683795073286467340682311713336473567943831090200965133", 10).unwrap());
-    
+    let public_key = &g_point * &private_key;
+    assert_eq!(*public_key.x()?, BigUint::parse_bytes(b"10781230418046409857141107048746558306281905541083370272873392624066644885158", 10).unwrap());
+    assert_eq!(*public_key.y()?, BigUint::parse_bytes(b"75292686749126855329828683795073286467340682311713336473567943831090200965133", 10).unwrap());
+
     let message = BigUint::from(12345u64);
-    let signature = sign_message(&message, &private_key, &g_point);
-    let is_valid = verify_signature(&signature, &message, &public_key, &g_point);
+    let signature = sign_message(&message, &private_key, &curve_config)?;
+    let is_valid = verify_signature(&signature, &message, &public_key, &curve_config)?;
     assert!(is_valid);
 
+    // Byte messages, hashed with SHA-256, signed with both a randomized and
+    // a deterministic (RFC 6979) nonce.
+    let msg = b"hello, ecdsa";
+    let digest = Sha256::digest(msg);
+    let hashed_message = BigUint::from_bytes_be(&digest) % &curve_config.n;
+    let randomized_signature = sign_bytes(msg, &private_key, &curve_config)?;
+    assert!(verify_signature(&randomized_signature, &hashed_message, &public_key, &curve_config)?);
+    let deterministic_signature = sign_bytes_deterministic(msg, &private_key, &curve_config)?;
+    assert!(verify_signature(&deterministic_signature, &hashed_message, &public_key, &curve_config)?);
+    assert_eq!(deterministic_signature, sign_bytes_deterministic(msg, &private_key, &curve_config)?);
+
+    // Point compression round trip.
+    let compressed = public_key.compress()?;
+    let decompressed = Point::decompress(&compressed, curve_config.clone())?;
+    assert_eq!(public_key, decompressed);
+
+    // Rebuilding secp256k1 from raw parameters validates the generator's
+    // order, exercising the registry's constructor alongside the built-in one.
+    let rebuilt_config = CurveConfig::from_params(
+        curve_config.a.clone(),
+        curve_config.b.clone(),
+        curve_config.p.clone(),
+        curve_config.n.clone(),
+        curve_config.gx.clone(),
+        curve_config.gy.clone(),
+    )?;
+    assert_eq!(rebuilt_config, curve_config);
+
     println!("Public key: {:#?}", public_key);
     println!("message: {:?}", message);
     println!("Signature: {:#?}", signature);
     println!("Is valid: {}", is_valid);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc6979_nonce_is_deterministic_and_matches_sign_bytes_deterministic() {
+        let curve_config = CurveConfig::secp256k1();
+        let private_key = BigUint::from(1u32);
+        let msg = b"sample";
+
+        let digest = Sha256::digest(msg);
+        let k1 = rfc6979_nonce(&private_key, &digest, &curve_config.n, 0).unwrap();
+        let k2 = rfc6979_nonce(&private_key, &digest, &curve_config.n, 0).unwrap();
+        assert_eq!(k1, k2);
+
+        let sig1 = sign_bytes_deterministic(msg, &private_key, &curve_config).unwrap();
+        let sig2 = sign_bytes_deterministic(msg, &private_key, &curve_config).unwrap();
+        assert_eq!(sig1, sig2);
+
+        // The signature's r comes from the first-attempt nonce k1.
+        let r_point = &curve_config.generator().unwrap() * &k1;
+        assert_eq!(sig1.0, r_point.x().unwrap() % &curve_config.n);
+    }
+
+    #[test]
+    fn rfc6979_retry_attempts_produce_distinct_nonces() {
+        let curve_config = CurveConfig::secp256k1();
+        let private_key = BigUint::from(42u32);
+        let digest = Sha256::digest(b"retry test");
+
+        let k0 = rfc6979_nonce(&private_key, &digest, &curve_config.n, 0).unwrap();
+        let k1 = rfc6979_nonce(&private_key, &digest, &curve_config.n, 1).unwrap();
+        assert_ne!(k0, k1);
+    }
+
+    #[test]
+    fn sign_bytes_and_sign_bytes_deterministic_round_trip() {
+        let curve_config = CurveConfig::secp256k1();
+        let g_point = curve_config.generator().unwrap();
+        let private_key = BigUint::from(777u32);
+        let public_key = &g_point * &private_key;
+        let msg = b"a byte message of arbitrary length";
+        let digest = Sha256::digest(msg);
+        let hashed_message = BigUint::from_bytes_be(&digest) % &curve_config.n;
+
+        let sig = sign_bytes(msg, &private_key, &curve_config).unwrap();
+        assert!(verify_signature(&sig, &hashed_message, &public_key, &curve_config).unwrap());
+
+        let det_sig = sign_bytes_deterministic(msg, &private_key, &curve_config).unwrap();
+        assert!(verify_signature(&det_sig, &hashed_message, &public_key, &curve_config).unwrap());
+    }
+
+    #[test]
+    fn modsqrt_fast_path_when_p_is_3_mod_4() {
+        // secp256k1's p ≡ 3 (mod 4), exercising the `s == 1` shortcut.
+        let curve_config = CurveConfig::secp256k1();
+        let value = BigUint::from(4u32);
+        let root = modsqrt(&value, &curve_config.p).unwrap();
+        assert_eq!((&root * &root) % &curve_config.p, value);
+    }
+
+    #[test]
+    fn modsqrt_general_case_when_p_is_1_mod_4() {
+        // p = 13 gives p - 1 = 12 = 3 * 2^2, so s == 2 and the general
+        // Tonelli-Shanks loop (rather than the `s == 1` shortcut) runs.
+        let modulus = BigUint::from(13u32);
+        let value = BigUint::from(4u32);
+        let root = modsqrt(&value, &modulus).unwrap();
+        assert_eq!((&root * &root) % &modulus, value);
+    }
+
+    #[test]
+    fn modsqrt_rejects_non_residue() {
+        let modulus = BigUint::from(13u32);
+        let non_residue = BigUint::from(2u32);
+        assert_eq!(modsqrt(&non_residue, &modulus), Err(EcError::NotOnCurve));
+    }
+
+    #[test]
+    fn compress_decompress_round_trip() {
+        let curve_config = CurveConfig::secp256k1();
+        let g_point = curve_config.generator().unwrap();
+        let public_key = &g_point * &BigUint::from(20220506u64);
+
+        let compressed = public_key.compress().unwrap();
+        let decompressed = Point::decompress(&compressed, curve_config).unwrap();
+        assert_eq!(public_key, decompressed);
+    }
+
+    #[test]
+    fn decompress_rejects_parity_mismatch_when_y_is_zero() {
+        // y^2 = x^3 over p = 13 has rhs == 0 at x == 0, whose only square
+        // root is y == 0 itself.
+        let curve_config = CurveConfig {
+            a: BigUint::zero(),
+            b: BigUint::zero(),
+            p: BigUint::from(13u32),
+            n: BigUint::from(13u32),
+            gx: BigUint::zero(),
+            gy: BigUint::zero(),
+        };
+        let mut bytes = [0u8; 33];
+
+        // The matching parity bit recovers y == 0, not the non-canonical
+        // `p - 0 == p`.
+        bytes[32] = 0x02;
+        let point = Point::decompress(&bytes, curve_config.clone()).unwrap();
+        assert_eq!(point, Point::Coor { x: BigUint::zero(), y: BigUint::zero(), curve_config: curve_config.clone() });
+
+        // The mismatched parity bit has no valid point to recover.
+        bytes[32] = 0x03;
+        assert_eq!(Point::decompress(&bytes, curve_config), Err(EcError::NotOnCurve));
+    }
+
+    #[test]
+    fn decompress_rejects_x_not_reduced_mod_p() {
+        // p == 13 and x == 13 (== p) is not a canonical field element, even
+        // though parsing it naively from bytes would succeed.
+        let curve_config = CurveConfig {
+            a: BigUint::zero(),
+            b: BigUint::zero(),
+            p: BigUint::from(13u32),
+            n: BigUint::from(13u32),
+            gx: BigUint::zero(),
+            gy: BigUint::zero(),
+        };
+        let mut bytes = [0u8; 33];
+        bytes[31] = 13;
+        bytes[32] = 0x02;
+        assert_eq!(Point::decompress(&bytes, curve_config), Err(EcError::NotOnCurve));
+    }
+
+    #[test]
+    fn from_params_accepts_the_real_secp256k1_generator() {
+        let secp256k1 = CurveConfig::secp256k1();
+        let rebuilt = CurveConfig::from_params(
+            secp256k1.a.clone(),
+            secp256k1.b.clone(),
+            secp256k1.p.clone(),
+            secp256k1.n.clone(),
+            secp256k1.gx.clone(),
+            secp256k1.gy.clone(),
+        )
+        .unwrap();
+        assert_eq!(rebuilt, secp256k1);
+    }
+
+    #[test]
+    fn from_params_rejects_a_generator_with_the_wrong_order() {
+        let secp256k1 = CurveConfig::secp256k1();
+        let wrong_n = &secp256k1.n - BigUint::one();
+        let result = CurveConfig::from_params(
+            secp256k1.a,
+            secp256k1.b,
+            secp256k1.p,
+            wrong_n,
+            secp256k1.gx,
+            secp256k1.gy,
+        );
+        assert_eq!(result, Err(EcError::InvalidScalar));
+    }
+
+    #[test]
+    fn from_params_rejects_a_non_minimal_multiple_of_the_order() {
+        // 2 * n also satisfies (2n) * G == Identity, since n * G already
+        // does, so the order check alone can't tell it apart from the true
+        // (prime) order.
+        let secp256k1 = CurveConfig::secp256k1();
+        let double_n = &secp256k1.n * BigUint::from(2u32);
+        let result = CurveConfig::from_params(
+            secp256k1.a,
+            secp256k1.b,
+            secp256k1.p,
+            double_n,
+            secp256k1.gx,
+            secp256k1.gy,
+        );
+        assert_eq!(result, Err(EcError::InvalidScalar));
+    }
+
+    #[test]
+    fn verify_signature_rejects_out_of_range_r_and_s_instead_of_erroring() {
+        let curve_config = CurveConfig::secp256k1();
+        let private_key = BigUint::from(123456789012345u64);
+        let public_key = &curve_config.generator().unwrap() * &private_key;
+        let message = BigUint::from(12345u64);
+
+        // s == 0 would otherwise make mod_inverse(s, n) fail with
+        // NonInvertible; it must be rejected as an invalid signature before
+        // that call, not propagated as an error.
+        let signature = (BigUint::one(), BigUint::zero());
+        assert_eq!(
+            verify_signature(&signature, &message, &public_key, &curve_config),
+            Ok(false)
+        );
+
+        let signature = (curve_config.n.clone(), BigUint::one());
+        assert_eq!(
+            verify_signature(&signature, &message, &public_key, &curve_config),
+            Ok(false)
+        );
+    }
 }